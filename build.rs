@@ -0,0 +1,282 @@
+// writes the OpenAPI 3 document of the admin api to OUT_DIR/openapi.json, read off the
+// handlers of the admin module: their route attributes, the comments above them, the
+// extractors they take and the responses they build
+
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+struct Handler {
+    method: String,
+    route: String,
+    name: String,
+    comment: Vec<String>,
+    signature: String,
+    body: String,
+}
+
+// the lines of `pub mod admin`, a module closes on its opening indent
+fn admin_module(source: &str) -> Vec<&str> {
+    let mut lines = source
+        .lines()
+        .skip_while(|line| line.trim_end() != "    pub mod admin {");
+    let mut module = vec![];
+    lines.next();
+    for line in lines {
+        if line.trim_end() == "    }" {
+            break;
+        }
+        module.push(line);
+    }
+    module
+}
+
+fn handlers(module: &[&str]) -> Vec<Handler> {
+    let mut handlers = vec![];
+    for (at, line) in module.iter().enumerate() {
+        let attribute = line.trim();
+        let method = ["get", "post", "put", "delete"]
+            .into_iter()
+            .find(|method| attribute.starts_with(&format!("#[{}(\"", method)));
+        let method = match method {
+            Some(method) => method,
+            None => continue,
+        };
+        let route = attribute[method.len() + 4..]
+            .trim_end_matches("\")]")
+            .to_string();
+        let mut comment: Vec<String> = module[..at]
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .take_while(|line| line.starts_with("//"))
+            .map(|line| line.trim_start_matches('/').trim().to_string())
+            .collect();
+        comment.reverse();
+        // the signature runs to the opening brace, the body to the brace closing at its indent
+        let rest = &module[at + 1..];
+        let open = rest
+            .iter()
+            .position(|line| line.trim_end().ends_with('{'))
+            .unwrap_or(0);
+        let signature = rest[..=open].join(" ");
+        let close = rest
+            .iter()
+            .position(|line| line.trim_end() == "        }")
+            .unwrap_or(rest.len());
+        let body = rest[open..close].join("\n");
+        let name = signature
+            .split("async fn ")
+            .nth(1)
+            .and_then(|rest| rest.split('(').next())
+            .unwrap_or_default()
+            .to_string();
+        handlers.push(Handler {
+            method: method.to_string(),
+            route,
+            name,
+            comment,
+            signature,
+            body,
+        });
+    }
+    handlers
+}
+
+// actix segments such as {name:.+} become {name}
+fn path_of(route: &str) -> (String, Vec<String>) {
+    let mut path = String::new();
+    let mut names = vec![];
+    let mut rest = route;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..]
+            .find('}')
+            .map(|close| open + close)
+            .unwrap_or(rest.len());
+        let name = rest[open + 1..close].split(':').next().unwrap_or_default();
+        path.push_str(&rest[..open]);
+        path.push_str(&format!("{{{}}}", name));
+        names.push(name.to_string());
+        rest = &rest[(close + 1).min(rest.len())..];
+    }
+    path.push_str(rest);
+    (path, names)
+}
+
+fn schema_of(rust: &str) -> Value {
+    match rust.trim() {
+        "u8" | "u16" | "u32" | "u64" | "usize" | "i32" | "i64" => json!({"type": "integer"}),
+        _ => json!({"type": "string"}),
+    }
+}
+
+// the types inside web::Path<..>, one per segment
+fn path_types(signature: &str) -> Vec<Value> {
+    let inner = match signature.split("web::Path<").nth(1) {
+        Some(rest) => rest.split('>').next().unwrap_or_default(),
+        None => return vec![],
+    };
+    inner
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .filter(|rust| !rust.trim().is_empty())
+        .map(schema_of)
+        .collect()
+}
+
+// the query.get("name") of the body and the ?name= mentioned in the comment, for handlers
+// taking a web::Query
+fn query_names(body: &str, comment: &str) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for part in body.split("query.get(\"").skip(1) {
+        let name = part.split('"').next().unwrap_or_default().to_string();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    for part in comment.split(['?', '&']).skip(1) {
+        let name: String = part
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        if !name.is_empty() && part[name.len()..].starts_with('=') && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+fn responses(body: &str) -> Value {
+    const STATUSES: [(&str, &str, &str); 11] = [
+        ("Ok", "200", "OK"),
+        ("Created", "201", "Created"),
+        ("Found", "302", "Found"),
+        ("BadRequest", "400", "Bad Request"),
+        ("Unauthorized", "401", "Unauthorized"),
+        ("Forbidden", "403", "Forbidden"),
+        ("NotFound", "404", "Not Found"),
+        ("Conflict", "409", "Conflict"),
+        ("InternalServerError", "500", "Internal Server Error"),
+        ("BadGateway", "502", "Bad Gateway"),
+        ("ServiceUnavailable", "503", "Service Unavailable"),
+    ];
+    let mut responses = BTreeMap::new();
+    for (builder, status, description) in STATUSES {
+        if body.contains(&format!("HttpResponse::{}()", builder)) {
+            responses.insert(status, json!({"description": description}));
+        }
+    }
+    // handlers returning web::Json or a bare body answer 200
+    if responses.is_empty() || body.contains("web::Json(") {
+        responses.insert("200", json!({"description": "OK"}));
+    }
+    // the content goes on the first success
+    let content = if body.contains("text/event-stream") {
+        Some(json!({"text/event-stream": {"schema": {"type": "string"}}}))
+    } else if body.contains("web::Json(") || body.contains(".json(") {
+        Some(json!({"application/json": {"schema": {}}}))
+    } else {
+        None
+    };
+    if let (Some(content), Some(ok)) = (content, responses.values_mut().next()) {
+        ok["content"] = content;
+    }
+    Value::Object(
+        responses
+            .into_iter()
+            .map(|(status, response)| (status.to_string(), response))
+            .collect(),
+    )
+}
+
+fn operation(handler: &Handler) -> Value {
+    let (_, names) = path_of(&handler.route);
+    let comment = handler.comment.join(" ");
+    let types = path_types(&handler.signature);
+    let mut parameters: Vec<Value> = names
+        .iter()
+        .enumerate()
+        .map(|(at, name)| {
+            let schema = types.get(at).cloned().unwrap_or(json!({"type": "string"}));
+            json!({"name": name, "in": "path", "required": true, "schema": schema})
+        })
+        .collect();
+    if handler.signature.contains("web::Query<") {
+        parameters.extend(query_names(&handler.body, &comment).into_iter().map(|name| {
+            json!({"name": name, "in": "query", "required": false, "schema": {"type": "string"}})
+        }));
+    }
+    let tag = handler
+        .route
+        .trim_start_matches('/')
+        .trim_start_matches("api/")
+        .split(['/', '{'])
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let mut operation = Map::new();
+    // the routes outside /api, such as /healthz and /metrics, go under ops
+    let tag = match tag.as_str() {
+        "" => String::from("dashboard"),
+        "healthz" | "readyz" | "metrics" => String::from("ops"),
+        _ => tag,
+    };
+    operation.insert("tags".into(), json!([tag]));
+    operation.insert("operationId".into(), json!(handler.name));
+    let summary = match handler.comment.first() {
+        Some(_) => comment.clone(),
+        None => handler.name.replace('_', " "),
+    };
+    operation.insert("summary".into(), json!(summary));
+    if !parameters.is_empty() {
+        operation.insert("parameters".into(), Value::Array(parameters));
+    }
+    if handler.signature.contains("web::Json<") {
+        operation.insert(
+            "requestBody".into(),
+            json!({"required": true, "content": {"application/json": {"schema": {"type": "object"}}}}),
+        );
+    }
+    operation.insert("responses".into(), responses(&handler.body));
+    Value::Object(operation)
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    let source = std::fs::read_to_string("src/lib.rs").expect("src/lib.rs");
+    let module = admin_module(&source);
+    let registered = module.join("\n");
+    let mut paths: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+    for handler in handlers(&module) {
+        // a handler the app never mounts is not served
+        if !registered.contains(&format!(".service({})", handler.name)) {
+            continue;
+        }
+        let (path, _) = path_of(&handler.route);
+        paths
+            .entry(path)
+            .or_default()
+            .insert(handler.method.clone(), operation(&handler));
+    }
+    let document = json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "rsms admin api",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "The admin listener of rsms.",
+        },
+        "security": [{}, {"basicAuth": []}],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "basicAuth": {
+                    "type": "http",
+                    "scheme": "basic",
+                    "description": "One of admin.users, required when it is set",
+                },
+            },
+        },
+    });
+    let out =
+        std::path::Path::new(&std::env::var("OUT_DIR").expect("OUT_DIR")).join("openapi.json");
+    std::fs::write(out, serde_json::to_string_pretty(&document).unwrap()).expect("openapi.json");
+}
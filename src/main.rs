@@ -1,9 +1,275 @@
+use lib::rsms::analyze;
+use lib::rsms::bench;
+use lib::rsms::config::{self, Config, RuntimeConfig};
 use lib::rsms::core::{Commander, Serve};
 use lib::rsms::infra::log;
+use lib::rsms::push;
+use lib::rsms::record;
+use lib::rsms::remux;
+use lib::rsms::runtime;
+use lib::rsms::upgrade;
+use std::path::Path;
+use std::time::Duration;
+
+const REMUX_USAGE: &str = "usage: rsms remux <input> <output> [--start <seconds>]";
+const PUSH_USAGE: &str = "usage: rsms push <file> <url> [--loop] [--copies <n>]";
+const BENCH_USAGE: &str = "usage: rsms bench [--publish <rtmp url>] [--publishers <n>] [--play <url>] [--subscribers <n>] [--duration <seconds>] [--bitrate <kbps>] [--json]";
+const PROBE_USAGE: &str = "usage: rsms probe <url or file> [--duration <seconds>] [--json]";
+
+// rsms remux <input> <output> [--start <seconds>], returns the exit code
+async fn remux_command(args: &[String]) -> i32 {
+    let (input, output) = match (args.first(), args.get(1)) {
+        (Some(input), Some(output)) => (input, output),
+        _ => {
+            eprintln!("{}", REMUX_USAGE);
+            return 2;
+        }
+    };
+    let start = match args.iter().position(|arg| arg == "--start") {
+        Some(i) => match args.get(i + 1).and_then(|s| s.parse::<f64>().ok()) {
+            Some(seconds) if seconds >= 0.0 => Some((seconds * 1000.0) as u32),
+            _ => {
+                eprintln!("{}", REMUX_USAGE);
+                return 2;
+            }
+        },
+        None => None,
+    };
+    match remux::run(Path::new(input), Path::new(output), start).await {
+        Ok(frames) => {
+            println!("remuxed {} frames from {} to {}", frames, input, output);
+            0
+        }
+        Err(e) => {
+            eprintln!("remux {} failed, {}", input, e);
+            1
+        }
+    }
+}
+
+// rsms probe <url or file> [--duration <seconds>] [--json], returns the exit code
+async fn probe_command(args: &[String]) -> i32 {
+    let input = match args.first().filter(|arg| !arg.starts_with("--")) {
+        Some(input) => input,
+        None => {
+            eprintln!("{}", PROBE_USAGE);
+            return 2;
+        }
+    };
+    let limit = match args.iter().position(|arg| arg == "--duration") {
+        Some(i) => match args.get(i + 1).and_then(|s| s.parse::<f64>().ok()) {
+            Some(seconds) if seconds > 0.0 => Duration::from_secs_f64(seconds),
+            _ => {
+                eprintln!("{}", PROBE_USAGE);
+                return 2;
+            }
+        },
+        None => Duration::from_secs(10),
+    };
+    match analyze::run(input, limit).await {
+        Ok(report) if args.iter().any(|arg| arg == "--json") => {
+            println!("{:#}", report.to_json());
+            0
+        }
+        Ok(report) => {
+            report.lines().iter().for_each(|line| println!("{}", line));
+            0
+        }
+        Err(e) => {
+            eprintln!("probe {} failed, {}", input, e);
+            1
+        }
+    }
+}
+
+// rsms push <file> <url> [--loop] [--copies <n>], returns the exit code
+async fn push_command(args: &[String]) -> i32 {
+    let (input, url) = match (args.first(), args.get(1)) {
+        (Some(input), Some(url)) if !input.starts_with("--") && !url.starts_with("--") => {
+            (input, url)
+        }
+        _ => {
+            eprintln!("{}", PUSH_USAGE);
+            return 2;
+        }
+    };
+    let copies = match args.iter().position(|arg| arg == "--copies") {
+        Some(i) => match args.get(i + 1).and_then(|s| s.parse::<usize>().ok()) {
+            Some(n) if n > 0 => n,
+            _ => {
+                eprintln!("{}", PUSH_USAGE);
+                return 2;
+            }
+        },
+        None => 1,
+    };
+    let looped = args.iter().any(|arg| arg == "--loop");
+    // a load test publishes the file to stream_1..stream_n at once
+    let urls: Vec<String> = match copies {
+        1 => vec![url.clone()],
+        n => (1..=n).map(|i| push::numbered(url, i)).collect(),
+    };
+    let pushes = urls
+        .iter()
+        .map(|url| push::run(Path::new(input).to_path_buf(), url.clone(), looped));
+    let results = futures::future::join_all(pushes).await;
+    let mut code = 0;
+    for (url, result) in urls.iter().zip(results) {
+        match result {
+            Ok(pushed) => println!(
+                "pushed {} frames, {} bytes to {} in {:.1} s",
+                pushed.frames,
+                pushed.bytes,
+                url,
+                pushed.elapsed.as_secs_f64()
+            ),
+            Err(e) => {
+                eprintln!("push {} failed, {}", url, e);
+                code = 1;
+            }
+        }
+    }
+    code
+}
+
+// rsms bench [--publish <rtmp url>] [--publishers <n>] [--play <url>] [--subscribers <n>]
+// [--duration <seconds>] [--bitrate <kbps>] [--json], returns the exit code
+async fn bench_command(args: &[String]) -> i32 {
+    let value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .map(|i| args.get(i + 1).cloned().ok_or(()))
+            .transpose()
+    };
+    let count = |flag: &str, default: usize| match value(flag) {
+        Ok(Some(n)) => n.parse::<usize>().map_err(|_| ()),
+        Ok(None) => Ok(default),
+        Err(_) => Err(()),
+    };
+    let (publish, play) = match (value("--publish"), value("--play")) {
+        (Ok(publish), Ok(play)) if publish.is_some() || play.is_some() => (publish, play),
+        _ => {
+            eprintln!("{}", BENCH_USAGE);
+            return 2;
+        }
+    };
+    let publishers = count("--publishers", publish.is_some() as usize);
+    let subscribers = count("--subscribers", play.is_some() as usize);
+    let duration = match value("--duration") {
+        Ok(Some(seconds)) => seconds.parse::<f64>().ok().filter(|s| *s > 0.0),
+        Ok(None) => Some(30.0),
+        Err(_) => None,
+    };
+    let bitrate = count("--bitrate", 1000);
+    let plan = match (publishers, subscribers, duration, bitrate) {
+        (Ok(publishers), Ok(subscribers), Some(seconds), Ok(bitrate)) => bench::Plan {
+            publish,
+            publishers,
+            play,
+            subscribers,
+            duration: Duration::from_secs_f64(seconds),
+            bitrate_kbps: bitrate as u32,
+        },
+        _ => {
+            eprintln!("{}", BENCH_USAGE);
+            return 2;
+        }
+    };
+    match bench::run(plan).await {
+        Ok(report) if args.iter().any(|arg| arg == "--json") => {
+            println!("{:#}", report.to_json());
+            0
+        }
+        Ok(report) => {
+            report.lines().iter().for_each(|line| println!("{}", line));
+            0
+        }
+        Err(e) => {
+            eprintln!("bench failed, {}", e);
+            1
+        }
+    }
+}
+
+// rsms repair <file>..., returns the exit code
+fn repair_command(files: &[String]) -> i32 {
+    if files.is_empty() {
+        eprintln!("usage: rsms repair <file>...");
+        return 2;
+    }
+    let mut code = 0;
+    for file in files {
+        match record::repair(Path::new(file)) {
+            Ok(repaired) => println!(
+                "repaired {}, {} ms, {} bytes, {} bytes dropped",
+                repaired.path.display(),
+                repaired.duration_ms,
+                repaired.bytes,
+                repaired.dropped
+            ),
+            Err(e) => {
+                eprintln!("repair {} failed, {}", file, e);
+                code = 1;
+            }
+        }
+    }
+    code
+}
+
+// the runtime is built from the config, so it is loaded first
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("remux") => {
+            let runtime =
+                runtime::build(&RuntimeConfig::default()).expect("failed to start the runtime");
+            std::process::exit(runtime.block_on(remux_command(&args[2..])))
+        }
+        Some("probe") => {
+            let runtime =
+                runtime::build(&RuntimeConfig::default()).expect("failed to start the runtime");
+            std::process::exit(runtime.block_on(probe_command(&args[2..])))
+        }
+        Some("push") => {
+            let runtime =
+                runtime::build(&RuntimeConfig::default()).expect("failed to start the runtime");
+            std::process::exit(runtime.block_on(push_command(&args[2..])))
+        }
+        Some("bench") => {
+            let runtime =
+                runtime::build(&RuntimeConfig::default()).expect("failed to start the runtime");
+            std::process::exit(runtime.block_on(bench_command(&args[2..])))
+        }
+        Some("repair") => std::process::exit(repair_command(&args[2..])),
+        _ => {}
+    }
 
-#[tokio::main]
-async fn main() {
     log::v("rsms initializing...");
+    if let Some(i) = args.iter().position(|arg| arg == "-c") {
+        let path = args.get(i + 1).expect("missing config path after -c");
+        match Config::load(path) {
+            Ok(conf) => config::init(conf),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // takes the listeners over from the running rsms, which then drains and exits
+    if args.iter().any(|arg| arg == "--upgrade") {
+        let path = &config::get().upgrade.socket;
+        if path.is_empty() {
+            panic!("--upgrade needs upgrade.socket in the config");
+        }
+        match upgrade::inherit(path) {
+            Ok(count) => println!("inherited {} listeners from {}", count, path),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    let runtime = runtime::build(&config::get().runtime).expect("failed to start the runtime");
+    runtime.block_on(serve());
+}
+
+async fn serve() {
     let commander = &mut Commander::new();
     commander.init();
     commander.start();